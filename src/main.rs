@@ -1,16 +1,28 @@
 
 use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use clap::Parser;
 use colored::Colorize;
+use encoding_rs::Encoding;
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
 use mime::Mime;
-use reqwest::{header, Client, Response, Url};
-use std::{collections::HashMap,str::FromStr};
+use reqwest::{
+    cookie::Jar,
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    multipart, Client, Method, Request as HttpRequest, Response, Url,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
 use syntect::{
     easy::HighlightLines,
     parsing::SyntaxSet,
     highlighting::{Style, ThemeSet},
     util::{as_24_bit_terminal_escaped, LinesWithEndings}
 };
+use tokio::io::AsyncWriteExt;
 
 // 以下部分用于处理CLI
 
@@ -26,66 +38,130 @@ struct Opts{
     subcmd: SubCommand,
 }
 
-// 子命令分别对应不同的HTTP方法，目前只支持get/post
+// 子命令分别对应不同的HTTP方法，每个方法都共用同一套Request语法
 #[derive(Parser, Debug)]
 enum SubCommand {
-    Get(Get),
-    Post(Post),
-    // 暂且不支持其他HTTP方法
+    Get(Request),
+    Post(Request),
+    Put(Request),
+    Patch(Request),
+    Delete(Request),
+    Head(Request),
 }
 
-// get子命令
-
-/// feed get with an url and will retrieve the response for you
-#[derive(Parser, Debug)]
-struct Get {
-    /// HTTP请求的URL
-    #[clap(parse(try_from_str = parse_url))]
-    url: String
+impl SubCommand {
+    /// 从子命令中取出对应的HTTP method和共用的Request参数
+    fn method_and_args(&self) -> (Method, &Request) {
+        match self {
+            SubCommand::Get(args) => (Method::GET, args),
+            SubCommand::Post(args) => (Method::POST, args),
+            SubCommand::Put(args) => (Method::PUT, args),
+            SubCommand::Patch(args) => (Method::PATCH, args),
+            SubCommand::Delete(args) => (Method::DELETE, args),
+            SubCommand::Head(args) => (Method::HEAD, args),
+        }
+    }
 }
 
-// post 子命令。 需要输入一个url
+// 所有HTTP方法子命令共用的参数： 一个url，加上若干个HTTPie风格的request-item
 
-/// feed post with an url and optional key=value pairs. We will post the data
-/// as JSON, and retrieve the response for you
+/// feed any HTTP method with an url and optional request items (HTTPie
+/// grammar: `k==v` query, `k:v` header, `k=v` JSON field, `k:=v` raw JSON
+/// value, `k@path` file content), and we will retrieve the response for you
 #[derive(Parser, Debug)]
-struct Post {
+struct Request {
     /// HTTP请求的URL
     #[clap(parse(try_from_str = parse_url))]
     url: String,
-    // HTTP请求的body
-    #[clap(parse(try_from_str = parse_kv_pair))]
-    body: Vec<KvPair>,
+    /// HTTP请求的query/header/body, 使用HTTPie的request-item语法
+    #[clap(parse(try_from_str = parse_item))]
+    items: Vec<RequestItem>,
+    /// 以application/x-www-form-urlencoded提交，而不是JSON
+    #[clap(long)]
+    form: bool,
+    /// 以multipart/form-data提交，`key@path`会作为文件附件上传
+    #[clap(long)]
+    multipart: bool,
+    /// 以流式方式把响应体下载保存到文件，而不是打印到终端
+    #[clap(short = 'd', long)]
+    download: bool,
+    /// 下载时保存的文件路径，缺省时从Content-Disposition或URL推断
+    #[clap(long)]
+    output: Option<String>,
+    /// 使用一个命名session来复用并保存headers和cookies
+    #[clap(long)]
+    session: Option<String>,
+    /// 配合--session使用，只读取session，不把本次请求的变化写回文件
+    #[clap(long)]
+    session_read_only: bool,
+    /// 通过HTTP/HTTPS/SOCKS5代理发送请求
+    #[clap(long)]
+    proxy: Option<String>,
+    /// HTTP Basic认证，格式为user:pass
+    #[clap(short = 'a', long)]
+    auth: Option<String>,
+    /// 使用Bearer token做认证
+    #[clap(long)]
+    bearer: Option<String>,
+    /// 设为no时不校验TLS证书，仅用于调试自签名证书
+    #[clap(long)]
+    verify: Option<String>,
+    /// 请求超时时间，单位为秒
+    #[clap(long)]
+    timeout: Option<u64>,
+    /// 只打印状态行和响应headers，不打印body
+    #[clap(long)]
+    headers: bool,
+    /// 只打印响应body，不打印状态行和headers
+    #[clap(short = 'b', long)]
+    body: bool,
+    /// 在发送前打印出本次请求的method、url、headers和body，可以和--headers/--body组合使用
+    #[clap(short = 'v', long)]
+    verbose: bool,
 }
 
-/// 命令中的key=value可以通过parse_kv_pair解析成KvPair结构
+/// 命令行中的每一个request-item, 按照HTTPie的语法区分成四类
 #[derive(Debug, PartialEq)]
-struct KvPair {
-    k: String,
-    v: String,
-}
-
-/// 当实现FromStr trait后，可以用str.parse()方法将字符串解析成KvPair
-impl FromStr for KvPair {
-    type Err = anyhow::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // 使用=进行split，这会得到一个迭代器
-        let mut split = s.split('=');
-        let err = || anyhow!(format!("Failed to parse {}", s));
-        Ok(Self {
-            // 从迭代器中取第一个结果作为key, 迭代器返回Some(T)/None
-            // 将其转换成Ok(T)/Err(E),然后用？处理错误
-            k: (split.next().ok_or_else(err)?).to_string(),
-            // 从迭代器中取第二个结果作为value
-            v: (split.next().ok_or_else(err)?).to_string(),
-        })
+enum RequestItem {
+    /// `key==value` 追加到URL的query string中
+    Query(String, String),
+    /// `key:value` 作为请求头, value为空表示移除一个已有的默认header
+    Header(String, String),
+    /// `key=value` 或 `key:=value`, 作为JSON body中的一个字段
+    Json(String, Value),
+    /// `key@path` 读取文件内容作为字段的值
+    File(String, String),
+}
+
+/// 在s中找到第一个HTTPie分隔符(==, :=, =, :, @)，返回(key, 分隔符, value)
+fn split_item(s: &str) -> Option<(&str, &str, &str)> {
+    let bytes = s.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'=' if bytes.get(i + 1) == Some(&b'=') => return Some((&s[..i], "==", &s[i + 2..])),
+            b':' if bytes.get(i + 1) == Some(&b'=') => return Some((&s[..i], ":=", &s[i + 2..])),
+            b'=' => return Some((&s[..i], "=", &s[i + 1..])),
+            b':' => return Some((&s[..i], ":", &s[i + 1..])),
+            b'@' => return Some((&s[..i], "@", &s[i + 1..])),
+            _ => continue,
+        }
     }
+    None
 }
 
-// 因为我们为KvPair实现了FromStr, 这里可以直接s.parse()得到KvPair
-fn parse_kv_pair(s: &str) -> Result<KvPair> {
-    s.parse()
+/// 将命令行中的一个request-item解析成RequestItem
+fn parse_item(s: &str) -> Result<RequestItem> {
+    let err = || anyhow!(format!("Failed to parse {}", s));
+    let (key, sep, value) = split_item(s).ok_or_else(err)?;
+    let key = key.to_string();
+    Ok(match sep {
+        "==" => RequestItem::Query(key, value.to_string()),
+        ":" => RequestItem::Header(key, value.to_string()),
+        ":=" => RequestItem::Json(key, serde_json::from_str(value)?),
+        "=" => RequestItem::Json(key, Value::String(value.to_string())),
+        "@" => RequestItem::File(key, value.to_string()),
+        _ => return Err(err()),
+    })
 }
 
 fn parse_url(s: &str) -> Result<String> {
@@ -94,47 +170,367 @@ fn parse_url(s: &str) -> Result<String> {
     Ok(s.into())
 }
 
-/// 处理get子命令
-// async fn get(client: Client, args: &Get) -> Result<()> {
-//     let resp = client.get(&args.url).send().await?;
-//     Ok(print_resp(resp).await?)
-// }
+// session: 把常用的headers/cookies保存到配置目录下，下次复用
 
+/// 持久化到磁盘的session内容：默认header和cookie
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Session {
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    cookies: Vec<String>,
+}
 
-// fn main() {
-//     let opts: Opts = Opts::parse();
-//     println!("{:?}", opts)
-// }
+/// 运行期间跟踪的session状态：磁盘内容
+struct SessionCtx {
+    name: String,
+    read_only: bool,
+    session: Session,
+}
 
-async fn get(client: Client, args: &Get) -> Result<()> {
-    let resp = client.get(&args.url).send().await?;
-    // println!("{:?}", resp.text().await?);
+/// session文件存放在系统配置目录(`dirs::config_dir()`，Linux下通常是`~/.config`)
+/// 下的`httpie/sessions/<name>.json`
+fn session_path(name: &str) -> Result<std::path::PathBuf> {
+    let mut dir = dirs::config_dir().ok_or_else(|| anyhow!("cannot find config directory"))?;
+    dir.push("httpie");
+    dir.push("sessions");
+    std::fs::create_dir_all(&dir)?;
+    dir.push(format!("{}.json", name));
+    Ok(dir)
+}
+
+fn load_session(name: &str) -> Result<Session> {
+    let path = session_path(name)?;
+    if path.exists() {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    } else {
+        Ok(Session::default())
+    }
+}
+
+fn save_session(name: &str, session: &Session) -> Result<()> {
+    let path = session_path(name)?;
+    std::fs::write(path, serde_json::to_string_pretty(session)?)?;
+    Ok(())
+}
 
-    Ok(print_resp(resp).await?)
+/// 把一个JSON Value转换成表单/multipart需要的文本形式: 字符串就取本身，其余类型按JSON文本展开
+fn json_value_to_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
 }
 
-async fn post(client: Client, args: &Post) -> Result<()> {
-    let mut body = HashMap::new();
-    for pair in args.body.iter() {
-        body.insert(&pair.k, &pair.v);
+/// 把解析出来的request-item组装成一个待发送的RequestBuilder。
+/// query/header items始终合并进URL和HeaderMap；剩下的json/file items
+/// 根据`--form`/`--multipart`决定body的编码方式：
+/// - 默认: 合并成一个JSON对象(`key@path`读取文件内容作为字符串字段)
+/// - `--form`: 合并成一个x-www-form-urlencoded表单
+/// - `--multipart`: `key=value`变成文本part, `key@path`变成文件part
+///
+/// headers从`default_headers`(auth/bearer/session里配置的那些)开始叠加，
+/// 这样命令行里的`key:value`/`key:`才能真正覆盖或移除这些默认值，
+/// 而不是作用在一个不包含它们的空HeaderMap上
+fn build_request(
+    client: &Client,
+    method: Method,
+    args: &Request,
+    default_headers: &HeaderMap,
+) -> Result<reqwest::RequestBuilder> {
+    let mut url: Url = args.url.parse()?;
+    let mut headers = default_headers.clone();
+    let mut json_fields = Vec::new();
+    let mut file_fields = Vec::new();
+
+    for item in args.items.iter() {
+        match item {
+            RequestItem::Query(k, v) => {
+                url.query_pairs_mut().append_pair(k, v);
+            }
+            RequestItem::Header(k, v) => {
+                let name = HeaderName::from_bytes(k.as_bytes())?;
+                if v.is_empty() {
+                    // 空value表示移除一个默认header
+                    headers.remove(&name);
+                } else {
+                    headers.insert(name, HeaderValue::from_str(v)?);
+                }
+            }
+            RequestItem::Json(k, v) => json_fields.push((k.clone(), v.clone())),
+            RequestItem::File(k, path) => file_fields.push((k.clone(), path.clone())),
+        }
     }
-    let resp = client.post(&args.url).json(&body).send().await?;
-    // println!("{:?}", resp.text().await?);
 
-    Ok(print_resp(resp).await?)
+    let mut builder = client.request(method, url).headers(headers);
+
+    if args.multipart {
+        let mut form = multipart::Form::new();
+        for (k, v) in json_fields {
+            form = form.text(k, json_value_to_text(&v));
+        }
+        for (k, path) in file_fields {
+            let bytes = std::fs::read(&path)?;
+            let file_name = std::path::Path::new(&path)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.clone());
+            let mime = mime_guess::from_path(&path).first_or_octet_stream();
+            let part = multipart::Part::bytes(bytes)
+                .file_name(file_name)
+                .mime_str(mime.as_ref())?;
+            form = form.part(k, part);
+        }
+        builder = builder.multipart(form);
+    } else if args.form {
+        let mut fields: Vec<(String, String)> = json_fields
+            .into_iter()
+            .map(|(k, v)| (k, json_value_to_text(&v)))
+            .collect();
+        for (k, path) in file_fields {
+            fields.push((k, std::fs::read_to_string(path)?));
+        }
+        builder = builder.form(&fields);
+    } else {
+        let mut body = serde_json::Map::new();
+        for (k, v) in json_fields {
+            body.insert(k, v);
+        }
+        for (k, path) in file_fields {
+            body.insert(k, Value::String(std::fs::read_to_string(path)?));
+        }
+        if !body.is_empty() {
+            builder = builder.json(&Value::Object(body));
+        }
+    }
+
+    Ok(builder)
+}
+
+/// 把`user:pass`编码成一个HTTP Basic认证的Authorization header值
+fn basic_auth_header(user_pass: &str) -> Result<HeaderValue> {
+    let encoded = STANDARD.encode(user_pass);
+    Ok(HeaderValue::from_str(&format!("Basic {}", encoded))?)
+}
+
+/// 根据命令行参数构造本次请求要使用的Client，以及一份待叠加到请求上的默认headers：
+/// `--proxy`/`--timeout`/`--verify=no`配置ClientBuilder本身；
+/// `-a/--auth`、`--bearer`生成默认的Authorization header；
+/// `--session`则额外装载session文件里的headers/cookies，
+/// 并把cookie jar交给ClientBuilder，这样响应里新增的cookie会自动被jar记录下来。
+/// 默认headers不直接放进`ClientBuilder::default_headers`，而是交还给调用方，
+/// 由`build_request`把它们和命令行的request-item合并, 这样`key:`才能真正
+/// 移除/覆盖一个默认header，而不是被reqwest在默认headers之外悄悄补回来
+fn build_client(args: &Request) -> Result<(Client, Option<SessionCtx>, HeaderMap)> {
+    let mut builder = Client::builder();
+
+    if let Some(proxy) = &args.proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    if let Some(secs) = args.timeout {
+        builder = builder.timeout(std::time::Duration::from_secs(secs));
+    }
+    if args.verify.as_deref() == Some("no") {
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    let mut default_headers = HeaderMap::new();
+    if let Some(user_pass) = &args.auth {
+        default_headers.insert(header::AUTHORIZATION, basic_auth_header(user_pass)?);
+    }
+    if let Some(token) = &args.bearer {
+        default_headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", token))?,
+        );
+    }
+
+    let ctx = match &args.session {
+        Some(name) => {
+            let session = load_session(name)?;
+            let jar = Arc::new(Jar::default());
+            let url: Url = args.url.parse()?;
+            for cookie in &session.cookies {
+                jar.add_cookie_str(cookie, &url);
+            }
+            for (k, v) in &session.headers {
+                default_headers
+                    .entry(HeaderName::from_bytes(k.as_bytes())?)
+                    .or_insert(HeaderValue::from_str(v)?);
+            }
+
+            builder = builder.cookie_provider(jar);
+            Some(SessionCtx {
+                name: name.clone(),
+                read_only: args.session_read_only,
+                session,
+            })
+        }
+        None => None,
+    };
+
+    Ok((builder.build()?, ctx, default_headers))
+}
+
+/// 把本次请求里用户设置的headers，以及响应里新下发的cookie写回session文件
+fn persist_session(ctx: &mut SessionCtx, args: &Request, resp: &Response) -> Result<()> {
+    for item in args.items.iter() {
+        if let RequestItem::Header(k, v) = item {
+            if v.is_empty() {
+                ctx.session.headers.remove(k);
+            } else {
+                ctx.session.headers.insert(k.clone(), v.clone());
+            }
+        }
+    }
+
+    let new_cookies = resp
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok().map(|s| s.to_string()));
+    merge_cookies(&mut ctx.session.cookies, new_cookies);
+
+    save_session(&ctx.name, &ctx.session)
+}
+
+/// 用本次响应里同名的Set-Cookie原样覆盖旧的那条，其余没有被重新下发的cookie保留不变。
+/// 必须保存完整的Set-Cookie字符串（而不是压扁成`name=value`），否则reload后
+/// Domain/Path/Secure/Expires等属性全部丢失：cookie会变成host-only且永不过期
+fn merge_cookies(existing: &mut Vec<String>, new_cookies: impl Iterator<Item = String>) {
+    for cookie in new_cookies {
+        let name = cookie_name(&cookie);
+        existing.retain(|c| cookie_name(c) != name);
+        existing.push(cookie);
+    }
+}
+
+/// 取出一条Set-Cookie字符串最前面的cookie名（`name=value; Domain=...`中的`name`）
+fn cookie_name(raw: &str) -> &str {
+    raw.split(';')
+        .next()
+        .and_then(|kv| kv.split('=').next())
+        .unwrap_or("")
+        .trim()
+}
+
+/// 处理任意HTTP方法的子命令
+async fn request(
+    client: Client,
+    method: Method,
+    args: &Request,
+    mut session_ctx: Option<SessionCtx>,
+    default_headers: HeaderMap,
+) -> Result<()> {
+    let req = build_request(&client, method, args, &default_headers)?.build()?;
+
+    // --verbose: 发送前把本次请求(method/url/headers/body)打印出来，
+    // 和--headers/--body选择的响应部分相互独立
+    if args.verbose {
+        print_request(&req);
+    }
+
+    let resp = client.execute(req).await?;
+
+    if let Some(ctx) = session_ctx.as_mut() {
+        if !ctx.read_only {
+            persist_session(ctx, args, &resp)?;
+        }
+    }
+
+    if args.download {
+        print_status(&resp);
+        print_headers(&resp);
+        download_resp(resp, args.output.as_deref()).await
+    } else {
+        // 默认(不带--headers/--body中任何一个)同时打印状态行+headers和body；
+        // 指定--headers或--body时只打印对应的部分
+        let show_headers = args.headers || !args.body;
+        let show_body = args.body || !args.headers;
+        print_resp(resp, show_headers, show_body).await
+    }
+}
+
+/// --verbose下，在发送前打印出本次请求的method、url、headers，以及body(如果是文本的话)
+fn print_request(req: &HttpRequest) {
+    let line = format!("{} {}", req.method(), req.url()).blue();
+    println!("{}", line);
+    for (name, value) in req.headers() {
+        println!("{}: {:?}", name.to_string().green(), value);
+    }
+    println!();
+    if let Some(text) = req
+        .body()
+        .and_then(|b| b.as_bytes())
+        .and_then(|b| std::str::from_utf8(b).ok())
+    {
+        println!("{}\n", text);
+    }
+}
+
+/// 从Content-Disposition header中取出filename="..."部分，并剥离路径分隔符/`..`，
+/// 避免服务端返回的文件名逃出当前目录（路径穿越）
+fn filename_from_content_disposition(value: &str) -> Option<String> {
+    value.split(';').find_map(|part| {
+        let f = part.trim().strip_prefix("filename=")?.trim_matches('"');
+        std::path::Path::new(f)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+    })
+}
+
+/// 从URL的最后一段路径中取出filename
+fn filename_from_url(url: &Url) -> Option<String> {
+    url.path_segments()?
+        .next_back()
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+/// 以流式方式把响应体写入文件，并用进度条展示下载进度
+async fn download_resp(resp: Response, output: Option<&str>) -> Result<()> {
+    let filename = output
+        .map(|s| s.to_string())
+        .or_else(|| {
+            resp.headers()
+                .get(header::CONTENT_DISPOSITION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(filename_from_content_disposition)
+        })
+        .or_else(|| filename_from_url(resp.url()))
+        .unwrap_or_else(|| "download".to_string());
+
+    let pb = match resp.content_length() {
+        Some(len) => ProgressBar::new(len),
+        None => ProgressBar::new_spinner(),
+    };
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{bar:40.cyan/blue} {bytes}/{total_bytes} ({eta})")
+            .unwrap()
+            .progress_chars("=>-"),
+    );
+
+    let mut file = tokio::fs::File::create(&filename).await?;
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        pb.inc(chunk.len() as u64);
+    }
+    pb.finish_with_message(format!("saved to {}", filename));
+
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let opts: Opts = Opts::parse();
-    // 生成一个HTTP客户端
-    let client = Client::new();
-    let result = match opts.subcmd {
-        SubCommand::Get(ref args) => get(client, args).await?,
-        SubCommand::Post(ref args) => post(client, args).await?,
-    };
-
-    Ok(result)
+    let (method, args) = opts.subcmd.method_and_args();
+    // 生成一个HTTP客户端，如果指定了--session则附带持久化的headers/cookies
+    let (client, session_ctx, default_headers) = build_client(args)?;
+    request(client, method, args, session_ctx, default_headers).await
 }
 
 // 打印服务器版本号 + 状态码
@@ -152,12 +548,9 @@ fn print_headers(resp: &Response) {
 }
 
 /// 打印服务器返回的HTTP body
-fn print_body(m: Option<Mime>, body: &String) {
+fn print_body(m: Option<Mime>, body: &str) {
     match m {
         // 对于"application/json", 我们pretty print
-        // Some(v) if v == mime::APPLICATION_JSON => {
-        //     println!("{}", jsonxf::pretty_print(body).unwrap().cyan());
-        // }
         Some(v) if v == mime::APPLICATION_JSON => print_syntect(body, "json"),
         Some(v) if v == mime::TEXT_HTML => print_syntect(body, "html"),
         // 其它 mime type, 直接输出
@@ -165,22 +558,65 @@ fn print_body(m: Option<Mime>, body: &String) {
     }
 }
 
-/// 打印整个响应
-async fn print_resp(resp: Response) -> Result<()> {
-    print_status(&resp);
-    print_headers(&resp);
-    let mime = get_content_type(&resp);
-    let body = resp.text().await?;
-    print_body(mime, &body);
-    
+/// 根据content type判断body是否应当当作文本展示：
+/// 顶层类型为text/的，以及子类型为常见文本负载(json/xml/javascript/
+/// x-www-form-urlencoded)或者以`+json`/`+xml`结尾的(不论顶层类型)都当作文本；
+/// 没有content-type时默认按文本尝试展示；其余一律当作二进制
+fn is_text_mime(m: Option<&Mime>) -> bool {
+    match m {
+        Some(m) => {
+            m.type_() == mime::TEXT
+                || matches!(
+                    m.subtype().as_str(),
+                    "json" | "xml" | "javascript" | "x-www-form-urlencoded"
+                )
+                || matches!(m.suffix().map(|s| s.as_str()), Some("json") | Some("xml"))
+        }
+        None => true,
+    }
+}
+
+/// 按content-type里的charset(缺省为utf-8)把原始字节解码成文本，
+/// 非文本类型(如图片/音视频等二进制body)返回None，调用方应避免把乱码打到终端
+fn decode_body(bytes: &[u8], m: Option<&Mime>) -> Option<String> {
+    if !is_text_mime(m) {
+        return None;
+    }
+    let charset = m.and_then(|m| m.get_param(mime::CHARSET));
+    let encoding = charset
+        .and_then(|charset| Encoding::for_label(charset.as_str().as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    let (text, _, _) = encoding.decode(bytes);
+    Some(text.into_owned())
+}
+
+/// 打印整个响应, show_headers/show_body控制是否输出对应的部分；
+/// 状态行归入headers部分，这样--body才能只输出body本身
+async fn print_resp(resp: Response, show_headers: bool, show_body: bool) -> Result<()> {
+    if show_headers {
+        print_status(&resp);
+        print_headers(&resp);
+    }
+    if show_body {
+        let mime = get_content_type(&resp);
+        let bytes = resp.bytes().await?;
+        match decode_body(&bytes, mime.as_ref()) {
+            Some(text) => print_body(mime, &text),
+            None => println!("+-----------------------------------------+\n| NOTE: binary data not shown in terminal |\n+-----------------------------------------+"),
+        }
+    }
+
     Ok(())
 }
 
-/// 将服务器返回的 content type解析成Mime类型
+/// 将服务器返回的 content type解析成Mime类型，缺失或无法解析时返回None而不是panic
 fn get_content_type(resp: &Response) -> Option<Mime> {
     resp.headers()
-        .get(header::CONTENT_TYPE)
-        .map(|v| v.to_str().unwrap().parse().unwrap())
+        .get(header::CONTENT_TYPE)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
 }
 
 fn print_syntect(s: &str, ext: &str) {
@@ -201,52 +637,194 @@ fn print_syntect(s: &str, ext: &str) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
     #[test]
     fn parse_url_works() {
         assert!(parse_url("abc").is_err());
         assert!(parse_url("http://abc.xyz").is_ok());
         assert!(parse_url("https://httpbin.org/post").is_ok());
     }
-    
+
     #[test]
-    fn parse_kv_pair_works() {
-        assert!(parse_kv_pair("a").is_err());
+    fn parse_item_query_works() {
         assert_eq!(
-            parse_kv_pair("a=1").unwrap(),
-            KvPair {
-                k: "a".into(),
-                v: "1".into(),
-            }
+            parse_item("key==value").unwrap(),
+            RequestItem::Query("key".into(), "value".into())
         );
-        
+    }
+
+    #[test]
+    fn parse_item_header_works() {
         assert_eq!(
-            parse_kv_pair("b=").unwrap(),
-            KvPair {
-                k: "b".into(),
-                v: "".into(),
-            }
+            parse_item("X-Token:abc").unwrap(),
+            RequestItem::Header("X-Token".into(), "abc".into())
+        );
+        // 空value表示移除一个默认header
+        assert_eq!(
+            parse_item("X-Token:").unwrap(),
+            RequestItem::Header("X-Token".into(), "".into())
         );
     }
-}
-
 
+    #[test]
+    fn parse_item_json_field_works() {
+        assert_eq!(
+            parse_item("a=1").unwrap(),
+            RequestItem::Json("a".into(), Value::String("1".into()))
+        );
+    }
 
+    #[test]
+    fn parse_item_raw_json_works() {
+        assert_eq!(
+            parse_item("n:=42").unwrap(),
+            RequestItem::Json("n".into(), Value::from(42))
+        );
+        assert_eq!(
+            parse_item("arr:=[1,2]").unwrap(),
+            RequestItem::Json("arr".into(), Value::from(vec![1, 2]))
+        );
+    }
 
+    #[test]
+    fn parse_item_file_works() {
+        assert_eq!(
+            parse_item("avatar@/tmp/a.png").unwrap(),
+            RequestItem::File("avatar".into(), "/tmp/a.png".into())
+        );
+    }
 
+    #[test]
+    fn parse_item_fails_without_separator() {
+        assert!(parse_item("noseparator").is_err());
+    }
 
+    #[test]
+    fn decode_body_defaults_to_utf8() {
+        let mime: Mime = "text/plain".parse().unwrap();
+        assert_eq!(
+            decode_body("你好".as_bytes(), Some(&mime)),
+            Some("你好".to_string())
+        );
+    }
 
+    #[test]
+    fn decode_body_honors_charset_param() {
+        let mime: Mime = "text/plain; charset=gbk".parse().unwrap();
+        let (encoded, _, _) = encoding_rs::GBK.encode("你好");
+        assert_eq!(
+            decode_body(&encoded, Some(&mime)),
+            Some("你好".to_string())
+        );
+    }
 
+    #[test]
+    fn decode_body_skips_binary_mime() {
+        let mime: Mime = "image/png".parse().unwrap();
+        assert_eq!(decode_body(b"\x89PNG", Some(&mime)), None);
+    }
 
+    #[test]
+    fn decode_body_defaults_to_text_without_content_type() {
+        assert_eq!(decode_body(b"hello", None), Some("hello".to_string()));
+    }
 
+    #[test]
+    fn basic_auth_header_encodes_user_pass() {
+        let header = basic_auth_header("user:pass").unwrap();
+        assert_eq!(header.to_str().unwrap(), "Basic dXNlcjpwYXNz");
+    }
 
+    #[test]
+    fn json_value_to_text_unwraps_strings() {
+        assert_eq!(json_value_to_text(&Value::String("hi".into())), "hi");
+    }
 
+    #[test]
+    fn json_value_to_text_stringifies_other_types() {
+        assert_eq!(json_value_to_text(&Value::from(42)), "42");
+        assert_eq!(json_value_to_text(&Value::Bool(true)), "true");
+    }
 
+    #[test]
+    fn filename_from_content_disposition_extracts_name() {
+        assert_eq!(
+            filename_from_content_disposition(r#"attachment; filename="report.pdf""#),
+            Some("report.pdf".to_string())
+        );
+    }
 
+    #[test]
+    fn filename_from_content_disposition_strips_path_traversal() {
+        // 恶意server塞进来的路径分量必须被剥掉，只留下文件名本身
+        assert_eq!(
+            filename_from_content_disposition(
+                r#"attachment; filename="../../../../home/user/.bashrc""#
+            ),
+            Some(".bashrc".to_string())
+        );
+        assert_eq!(
+            filename_from_content_disposition(r#"attachment; filename="/etc/passwd""#),
+            Some("passwd".to_string())
+        );
+    }
 
+    #[test]
+    fn filename_from_content_disposition_missing_filename() {
+        assert_eq!(filename_from_content_disposition("inline"), None);
+    }
 
+    #[test]
+    fn filename_from_url_takes_last_segment() {
+        let url: Url = "https://httpbin.org/files/report.pdf".parse().unwrap();
+        assert_eq!(filename_from_url(&url), Some("report.pdf".to_string()));
+    }
 
+    #[test]
+    fn filename_from_url_empty_path_yields_none() {
+        let url: Url = "https://httpbin.org/".parse().unwrap();
+        assert_eq!(filename_from_url(&url), None);
+    }
 
+    #[test]
+    fn cookie_name_extracts_name_before_attributes() {
+        assert_eq!(cookie_name("a=1; Domain=example.com; Secure"), "a");
+        assert_eq!(cookie_name("a=1"), "a");
+    }
 
+    #[test]
+    fn merge_cookies_appends_new_cookie() {
+        let mut existing = vec!["a=1".to_string()];
+        merge_cookies(&mut existing, vec!["b=2; Path=/".to_string()].into_iter());
+        assert_eq!(existing, vec!["a=1".to_string(), "b=2; Path=/".to_string()]);
+    }
 
+    #[test]
+    fn merge_cookies_replaces_same_named_cookie_and_keeps_its_attributes() {
+        let mut existing = vec!["a=1; Domain=example.com; Secure".to_string()];
+        merge_cookies(
+            &mut existing,
+            vec!["a=2; Domain=example.com".to_string()].into_iter(),
+        );
+        assert_eq!(existing, vec!["a=2; Domain=example.com".to_string()]);
+    }
 
+    #[test]
+    fn merge_cookies_keeps_cookies_not_resent_this_time() {
+        let mut existing = vec![
+            "a=1; Domain=example.com".to_string(),
+            "b=2; Domain=example.com".to_string(),
+        ];
+        merge_cookies(
+            &mut existing,
+            vec!["a=9; Domain=example.com".to_string()].into_iter(),
+        );
+        assert_eq!(
+            existing,
+            vec![
+                "b=2; Domain=example.com".to_string(),
+                "a=9; Domain=example.com".to_string(),
+            ]
+        );
+    }
+}